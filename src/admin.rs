@@ -0,0 +1,91 @@
+use futures::Future;
+use rdkafka::admin::{AdminClient, AdminOptions, AlterConfig, ConfigResourceResult, NewPartitions, NewTopic, ResourceSpecifier, TopicReplication, TopicResult};
+use rdkafka::client::DefaultClientContext;
+
+use error::*;
+
+/// Parameters for creating a new topic. Mirrors rdkafka's `NewTopic` builder,
+/// but keeps the admin module decoupled from the rest of the crate.
+pub struct NewTopicSpec {
+    pub name: String,
+    pub num_partitions: i32,
+    pub replication_factor: i32,
+    pub config: Vec<(String, String)>
+}
+
+/// Parameters for growing an existing topic's partition count.
+pub struct NewPartitionsSpec {
+    pub topic: String,
+    pub new_partition_count: usize
+}
+
+/// Parameters for altering the configuration of a single topic.
+pub struct AlterConfigSpec {
+    pub topic: String,
+    pub entries: Vec<(String, String)>
+}
+
+fn check_results(results: Vec<TopicResult>) -> Result<()> {
+    let mut errors = Vec::new();
+    for result in results {
+        if let Err((resource, err_code)) = result {
+            errors.push(format!("{}: {}", resource, err_code));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        bail!(format!("Admin operation failed: {}", errors.join(", ")))
+    }
+}
+
+fn check_config_results(results: Vec<ConfigResourceResult>) -> Result<()> {
+    let mut errors = Vec::new();
+    for result in results {
+        if let Err((resource, err_code)) = result {
+            errors.push(format!("{:?}: {}", resource, err_code));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        bail!(format!("Admin operation failed: {}", errors.join(", ")))
+    }
+}
+
+pub fn create_topic(admin_client: &AdminClient<DefaultClientContext>, spec: &NewTopicSpec, timeout_ms: i32) -> Result<()> {
+    let mut new_topic = NewTopic::new(&spec.name, spec.num_partitions, TopicReplication::Fixed(spec.replication_factor));
+    for &(ref key, ref value) in &spec.config {
+        new_topic = new_topic.set(key, value);
+    }
+    let opts = AdminOptions::new().operation_timeout(Some(timeout_ms));
+    let results = admin_client.create_topics(&[new_topic], &opts).wait()
+        .chain_err(|| format!("Failed to create topic: {}", spec.name))?;
+    check_results(results)
+}
+
+pub fn delete_topic(admin_client: &AdminClient<DefaultClientContext>, topic: &str, timeout_ms: i32) -> Result<()> {
+    let opts = AdminOptions::new().operation_timeout(Some(timeout_ms));
+    let results = admin_client.delete_topics(&[topic], &opts).wait()
+        .chain_err(|| format!("Failed to delete topic: {}", topic))?;
+    check_results(results)
+}
+
+pub fn create_partitions(admin_client: &AdminClient<DefaultClientContext>, spec: &NewPartitionsSpec, timeout_ms: i32) -> Result<()> {
+    let new_partitions = NewPartitions::new(&spec.topic, spec.new_partition_count);
+    let opts = AdminOptions::new().operation_timeout(Some(timeout_ms));
+    let results = admin_client.create_partitions(&[new_partitions], &opts).wait()
+        .chain_err(|| format!("Failed to add partitions to topic: {}", spec.topic))?;
+    check_results(results)
+}
+
+pub fn alter_configs(admin_client: &AdminClient<DefaultClientContext>, spec: &AlterConfigSpec, timeout_ms: i32) -> Result<()> {
+    let mut alter_config = AlterConfig::new(ResourceSpecifier::Topic(&spec.topic));
+    for &(ref key, ref value) in &spec.entries {
+        alter_config = alter_config.set(key, value);
+    }
+    let opts = AdminOptions::new().operation_timeout(Some(timeout_ms));
+    let results = admin_client.alter_configs(&[alter_config], &opts).wait()
+        .chain_err(|| format!("Failed to alter config for topic: {}", spec.topic))?;
+    check_config_results(results)
+}
@@ -1,14 +1,25 @@
 use chrono::{DateTime, UTC};
+use serde_json;
 use rdkafka::consumer::{BaseConsumer, EmptyConsumerContext};
 use rdkafka::config::ClientConfig;
 use rdkafka::error as rderror;
+use rdkafka::topic_partition_list::{TopicPartitionList, Offset};
 
+use rdkafka::admin::AdminClient;
+use rdkafka::client::DefaultClientContext;
+
+use admin;
+use admin::{AlterConfigSpec, NewPartitionsSpec, NewTopicSpec};
+use metrics::{ClusterGauges, MetricsSink};
+use storage::{self, ObjectStore};
 use error::*;
 use scheduler::{Scheduler, ScheduledTask};
 use cache::ReplicatedMap;
+use std::cmp;
+use std::thread;
 use std::time::Duration;
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 // TODO: Use structs?
 pub type BrokerId = i32;
@@ -25,7 +36,9 @@ pub struct Partition {
     pub leader: BrokerId,
     pub replicas: Vec<BrokerId>,
     pub isr: Vec<BrokerId>,
-    pub error: Option<String>
+    pub error: Option<String>,
+    pub low_watermark: Option<i64>,
+    pub high_watermark: Option<i64>
 }
 
 impl Partition {
@@ -35,7 +48,17 @@ impl Partition {
             leader: leader,
             replicas: replicas,
             isr: isr,
-            error: error
+            error: error,
+            low_watermark: None,
+            high_watermark: None
+        }
+    }
+
+    /// Number of messages in the partition, if watermarks have been fetched.
+    pub fn message_count(&self) -> Option<i64> {
+        match (self.low_watermark, self.high_watermark) {
+            (Some(low), Some(high)) => Some(high - low),
+            _ => None
         }
     }
 }
@@ -97,6 +120,36 @@ fn fetch_metadata(consumer: &BaseConsumer<EmptyConsumerContext>, timeout_ms: i32
     Ok(Metadata::new(brokers, topics))
 }
 
+// Watermark fetches are best-effort per partition: a single leaderless or
+// offline partition is routine in real clusters and must not stop topic
+// metadata, groups, lag, or metrics from updating for the rest of the
+// cluster. Failures are left as a `None` watermark rather than aborting.
+//
+// Issued sequentially on the cluster's own consumer handle: librdkafka
+// serializes requests on a single client instance internally, so fanning
+// these out over extra threads bought no real parallelism, just thread
+// churn every tick.
+fn fetch_watermarks(cluster_id: &ClusterId, consumer: &BaseConsumer<EmptyConsumerContext>, topics: &mut BTreeMap<TopicName, Vec<Partition>>, timeout_ms: i32) {
+    let mut total = 0;
+    let mut failed_count = 0;
+    for (topic, partitions) in topics.iter_mut() {
+        for partition in partitions.iter_mut() {
+            total += 1;
+            match consumer.fetch_watermarks(topic, partition.id, timeout_ms) {
+                Ok((low, high)) => {
+                    partition.low_watermark = Some(low);
+                    partition.high_watermark = Some(high);
+                },
+                Err(_) => failed_count += 1
+            }
+        }
+    }
+
+    if failed_count > 0 {
+        warn!("Failed to fetch watermarks for {} of {} partitions, cluster: {}", failed_count, total, cluster_id);
+    }
+}
+
 //
 // ********** GROUPS **********
 //
@@ -106,6 +159,7 @@ pub struct GroupMember {
     pub id: String,
     pub client_id: String,
     pub client_host: String,
+    pub assigned_topics: Vec<TopicName>
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -115,6 +169,63 @@ pub struct Group {
     pub members: Vec<GroupMember>
 }
 
+impl Group {
+    // The topics this group is actually consuming from, derived from each
+    // member's partition assignment rather than the whole cluster.
+    fn subscribed_topics(&self) -> Vec<TopicName> {
+        let mut topics = self.members.iter()
+            .flat_map(|member| member.assigned_topics.iter().cloned())
+            .collect::<Vec<_>>();
+        topics.sort();
+        topics.dedup();
+        topics
+    }
+}
+
+// Minimal reader for the subset of the Kafka consumer group assignment
+// encoding (`ConsumerProtocolAssignment`) we need: just the assigned topic
+// names, not the partitions or user data.
+fn read_i16(bytes: &[u8], offset: usize) -> Option<i16> {
+    bytes.get(offset..offset + 2).map(|b| ((b[0] as i16) << 8) | (b[1] as i16))
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    bytes.get(offset..offset + 4)
+        .map(|b| ((b[0] as i32) << 24) | ((b[1] as i32) << 16) | ((b[2] as i32) << 8) | (b[3] as i32))
+}
+
+fn parse_assigned_topics(assignment: &[u8]) -> Vec<TopicName> {
+    // version: i16, then an array of (topic: string, partitions: [i32]).
+    let mut offset = 2;
+    let topic_count = match read_i32(assignment, offset) {
+        Some(count) if count > 0 => count,
+        _ => return Vec::new()
+    };
+    offset += 4;
+
+    let mut topics = Vec::new();
+    for _ in 0..topic_count {
+        let name_len = match read_i16(assignment, offset) {
+            Some(len) if len >= 0 => len as usize,
+            _ => break
+        };
+        offset += 2;
+        let name = match assignment.get(offset..offset + name_len) {
+            Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+            None => break
+        };
+        offset += name_len;
+        topics.push(name);
+
+        let partition_count = match read_i32(assignment, offset) {
+            Some(count) if count >= 0 => count as usize,
+            _ => break
+        };
+        offset += 4 + partition_count * 4;
+    }
+    topics
+}
+
 fn fetch_groups(consumer: &BaseConsumer<EmptyConsumerContext>, timeout_ms: i32) -> Result<Vec<Group>> {
     let group_list = consumer.fetch_group_list(None, timeout_ms)
         .chain_err(|| "Failed to fetch consumer group list")?;
@@ -125,7 +236,8 @@ fn fetch_groups(consumer: &BaseConsumer<EmptyConsumerContext>, timeout_ms: i32)
             .map(|m| GroupMember {
                 id: m.id().to_owned(),
                 client_id: m.client_id().to_owned(),
-                client_host: m.client_host().to_owned()
+                client_host: m.client_host().to_owned(),
+                assigned_topics: m.assignment().map(parse_assigned_topics).unwrap_or_default()
             })
             .collect::<Vec<_>>();
         groups.push(Group {
@@ -137,60 +249,254 @@ fn fetch_groups(consumer: &BaseConsumer<EmptyConsumerContext>, timeout_ms: i32)
     Ok(groups)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupOffset {
+    pub topic: TopicName,
+    pub partition: i32,
+    pub committed: i64,
+    pub high_watermark: i64,
+    pub lag: Option<i64>,
+    pub error: Option<String>
+}
+
+// No commit for this partition: any negative offset (rdkafka's Invalid
+// marker, or a raw -1) means the group has never committed here.
+const NO_COMMIT_OFFSET: i64 = -1001;
+
+// A consumer bound to a given group.id is reused across ticks instead of
+// being recreated every 30s for every group, mirroring LazyConsumer.
+type GroupConsumers = Mutex<BTreeMap<String, Arc<BaseConsumer<EmptyConsumerContext>>>>;
+
+fn ensure_group_consumer(group_consumers: &GroupConsumers, boostrap_servers: &str, group_name: &str) -> Result<Arc<BaseConsumer<EmptyConsumerContext>>> {
+    let mut guard = group_consumers.lock().unwrap();
+    if let Some(consumer) = guard.get(group_name) {
+        return Ok(consumer.clone());
+    }
+    let created = ClientConfig::new()
+        .set("bootstrap.servers", boostrap_servers)
+        .set("group.id", group_name)
+        .create::<BaseConsumer<_>>()
+        .chain_err(|| format!("Failed to create consumer for group: {}", group_name))
+        .map(Arc::new)?;
+    guard.insert(group_name.to_owned(), created.clone());
+    Ok(created)
+}
+
+fn fetch_group_offsets(group_consumers: &GroupConsumers, boostrap_servers: &str, metadata: &Metadata, group: &Group, timeout_ms: i32) -> Result<Vec<GroupOffset>> {
+    let group_consumer = ensure_group_consumer(group_consumers, boostrap_servers, &group.name)?;
+
+    // Members only have a live assignment while actively consuming; a group
+    // sitting idle between batches (state "Empty") still has committed
+    // offsets worth reporting, so fall back to every cluster topic when
+    // there's no assignment to narrow the search.
+    let mut subscribed_topics = group.subscribed_topics();
+    if subscribed_topics.is_empty() {
+        subscribed_topics = metadata.topics.keys().cloned().collect();
+    }
+
+    let mut tpl = TopicPartitionList::new();
+    for topic in subscribed_topics {
+        if let Some(partitions) = metadata.topics.get(&topic) {
+            for partition in partitions {
+                tpl.add_partition(&topic, partition.id);
+            }
+        }
+    }
+
+    let committed = group_consumer.committed_offsets(tpl, timeout_ms)
+        .chain_err(|| format!("Failed to fetch committed offsets for group: {}", group.name))?;
+
+    let mut offsets = Vec::new();
+    for elem in committed.elements() {
+        let topic = elem.topic().to_owned();
+        let partition = elem.partition();
+        let committed_offset = match elem.offset() {
+            Offset::Offset(offset) => offset,
+            _ => NO_COMMIT_OFFSET
+        };
+
+        if committed_offset < 0 {
+            continue;
+        }
+
+        match group_consumer.fetch_watermarks(&topic, partition, timeout_ms) {
+            Ok((_low, high)) => {
+                offsets.push(GroupOffset {
+                    topic: topic,
+                    partition: partition,
+                    committed: committed_offset,
+                    high_watermark: high,
+                    lag: Some(high - committed_offset),
+                    error: None
+                });
+            },
+            Err(e) => {
+                offsets.push(GroupOffset {
+                    topic: topic,
+                    partition: partition,
+                    committed: committed_offset,
+                    high_watermark: -1,
+                    lag: None,
+                    error: Some(e.to_string())
+                });
+            }
+        }
+    }
+
+    Ok(offsets)
+}
+
+
+// A cluster's consumer is created lazily and shared between the scheduled
+// task and MetadataFetcher's own admin/refresh calls, so a cluster that is
+// unreachable at startup doesn't take the rest down with it.
+type LazyConsumer = Arc<Mutex<Option<Arc<BaseConsumer<EmptyConsumerContext>>>>>;
+
+fn ensure_consumer(consumer: &LazyConsumer, boostrap_servers: &str) -> Result<Arc<BaseConsumer<EmptyConsumerContext>>> {
+    let mut guard = consumer.lock().unwrap();
+    if let Some(ref consumer) = *guard {
+        return Ok(consumer.clone());
+    }
+    let created = ClientConfig::new()
+        .set("bootstrap.servers", boostrap_servers)
+        .create::<BaseConsumer<_>>()
+        .chain_err(|| "Failed to create consumer")
+        .map(Arc::new)?;
+    *guard = Some(created.clone());
+    Ok(created)
+}
+
+// Same lazy-creation pattern as LazyConsumer: a cluster whose admin client
+// can't be built at startup shouldn't take the rest of the process down
+// with it, so creation is deferred to the first admin operation.
+type LazyAdminClient = Arc<Mutex<Option<Arc<AdminClient<DefaultClientContext>>>>>;
+
+fn ensure_admin_client(admin_client: &LazyAdminClient, boostrap_servers: &str) -> Result<Arc<AdminClient<DefaultClientContext>>> {
+    let mut guard = admin_client.lock().unwrap();
+    if let Some(ref admin_client) = *guard {
+        return Ok(admin_client.clone());
+    }
+    let created = ClientConfig::new()
+        .set("bootstrap.servers", boostrap_servers)
+        .create::<AdminClient<_>>()
+        .chain_err(|| "Failed to create admin client")
+        .map(Arc::new)?;
+    *guard = Some(created.clone());
+    Ok(created)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FetchStatus {
+    pub last_success: DateTime<UTC>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32
+}
+
+const RETRY_MAX_ATTEMPTS: u32 = 5;
+const RETRY_INITIAL_DELAY_MS: u64 = 500;
+const RETRY_MAX_DELAY_MS: u64 = 8000;
+
+// Retries a single cluster's fetch step with exponential backoff, so one
+// slow or unreachable cluster degrades gracefully instead of failing the
+// whole tick on the first transient error.
+fn retry_with_backoff<T, F: FnMut() -> Result<T>>(description: &str, mut f: F) -> Result<T> {
+    let mut delay_ms = RETRY_INITIAL_DELAY_MS;
+    for attempt in 1..(RETRY_MAX_ATTEMPTS + 1) {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                if attempt == RETRY_MAX_ATTEMPTS {
+                    return Err(err).chain_err(|| format!("{} failed after {} attempts", description, RETRY_MAX_ATTEMPTS));
+                }
+                warn!("{} failed (attempt {}/{}), retrying in {}ms: {}", description, attempt, RETRY_MAX_ATTEMPTS, delay_ms, err);
+                thread::sleep(Duration::from_millis(delay_ms));
+                delay_ms = cmp::min(delay_ms * 2, RETRY_MAX_DELAY_MS);
+            }
+        }
+    }
+    unreachable!()
+}
 
 // TODO: remove and use MetadataFetcher directly
 struct MetadataFetcherTask {
     cluster_id: ClusterId,
     boostrap_servers: String,
-    consumer: Option<BaseConsumer<EmptyConsumerContext>>,
+    consumer: LazyConsumer,
+    group_consumers: GroupConsumers,
+    metrics_sink: Option<Arc<MetricsSink>>,
+    object_store: Option<Arc<ObjectStore>>,
+    status: Mutex<FetchStatus>,
     cache: ReplicatedMap<ClusterId, Arc<Metadata>>,
     broker_cache: ReplicatedMap<ClusterId, Vec<Broker>>,
     topic_cache: ReplicatedMap<(ClusterId, TopicName), Vec<Partition>>,
-    group_cache: ReplicatedMap<(ClusterId, String), Group>
+    group_cache: ReplicatedMap<(ClusterId, String), Group>,
+    group_offsets_cache: ReplicatedMap<(ClusterId, String), Vec<GroupOffset>>,
+    fetch_status_cache: ReplicatedMap<ClusterId, FetchStatus>
 }
 
 impl MetadataFetcherTask {
     fn new(
         cluster_id: &ClusterId,
         boostrap_servers: &str,
+        consumer: LazyConsumer,
         cache: ReplicatedMap<ClusterId, Arc<Metadata>>,
         broker_cache: ReplicatedMap<ClusterId, Vec<Broker>>,
         topic_cache: ReplicatedMap<(ClusterId, TopicName), Vec<Partition>>,
-        group_cache: ReplicatedMap<(ClusterId, String), Group>
+        group_cache: ReplicatedMap<(ClusterId, String), Group>,
+        group_offsets_cache: ReplicatedMap<(ClusterId, String), Vec<GroupOffset>>,
+        metrics_sink: Option<Arc<MetricsSink>>,
+        object_store: Option<Arc<ObjectStore>>,
+        fetch_status_cache: ReplicatedMap<ClusterId, FetchStatus>
     ) -> MetadataFetcherTask {
         MetadataFetcherTask {
             cluster_id: cluster_id.to_owned(),
             boostrap_servers: boostrap_servers.to_owned(),
-            consumer: None,
+            consumer: consumer,
+            group_consumers: Mutex::new(BTreeMap::new()),
+            metrics_sink: metrics_sink,
+            object_store: object_store,
+            status: Mutex::new(FetchStatus {
+                last_success: UTC::now(),
+                last_error: None,
+                consecutive_failures: 0
+            }),
             cache: cache,
             broker_cache: broker_cache,
             topic_cache: topic_cache,
             group_cache: group_cache,
+            group_offsets_cache: group_offsets_cache,
+            fetch_status_cache: fetch_status_cache,
         }
     }
-
-    fn create_consumer(&mut self) {
-        let consumer = ClientConfig::new()
-            .set("bootstrap.servers", &self.boostrap_servers)
-            .create::<BaseConsumer<_>>()
-            .expect("Consumer creation failed");
-        self.consumer = Some(consumer);
-    }
 }
 
-impl ScheduledTask for MetadataFetcherTask {
-    fn run(&self) -> Result<()> {
+impl MetadataFetcherTask {
+    fn run_once(&self) -> Result<()> {
         // Old metadata fetch
         debug!("Metadata fetch start");
-        let ref consumer = self.consumer.as_ref().ok_or_else(|| "Consumer not initialized")?;
-        let metadata = fetch_metadata(consumer, 30000)
-            .chain_err(|| format!("Metadata fetch failed, cluster: {}", self.cluster_id))?;
+        let consumer = retry_with_backoff(&format!("Consumer connect, cluster: {}", self.cluster_id), || {
+            ensure_consumer(&self.consumer, &self.boostrap_servers)
+        })?;
+        let own_metadata = retry_with_backoff(&format!("Metadata fetch, cluster: {}", self.cluster_id), || {
+            fetch_metadata(&consumer, 30000)
+        })?;
         debug!("Metadata fetch end");
-        self.cache.insert(self.cluster_id.to_owned(), Arc::new(metadata))
+        self.cache.insert(self.cluster_id.to_owned(), Arc::new(own_metadata.clone()))
             .chain_err(|| "Failed to create new metadata container to cache")?;
+
+        if let Some(ref store) = self.object_store {
+            let key = format!("{}/metadata/{}.json", self.cluster_id, own_metadata.refresh_time.to_rfc3339());
+            let snapshot = serde_json::to_vec(&own_metadata)
+                .chain_err(|| "Failed to serialize metadata snapshot")
+                .and_then(|bytes| store.put(&key, bytes));
+            if let Err(err) = snapshot {
+                warn!("Failed to store metadata snapshot, cluster: {}, error: {}", self.cluster_id, err);
+            }
+        }
         // New metadata fetch
-        let metadata = self.consumer.as_ref().unwrap().fetch_metadata(30000)
-            .chain_err(|| "Failed to fetch metadata from consumer")?;
+        let metadata = retry_with_backoff(&format!("Metadata fetch, cluster: {}", self.cluster_id), || {
+            consumer.fetch_metadata(30000).chain_err(|| "Failed to fetch metadata from consumer")
+        })?;
         let mut brokers = Vec::new();
         for broker in metadata.brokers() {
             brokers.push(Broker::new(broker.id(), broker.host().to_owned(), broker.port()));
@@ -198,33 +504,112 @@ impl ScheduledTask for MetadataFetcherTask {
         self.broker_cache.insert(self.cluster_id.to_owned(), brokers)
             .chain_err(|| "Failed to insert broker information in cache")?;
 
+        let mut topics = BTreeMap::new();
+        let mut partition_count = 0i64;
+        let mut under_replicated_partitions = 0i64;
+        let mut offline_partitions = 0i64;
         for topic in metadata.topics() {
             let mut partitions = Vec::with_capacity(topic.partitions().len());
             for p in topic.partitions() {
+                partition_count += 1;
+                if p.isr().len() < p.replicas().len() {
+                    under_replicated_partitions += 1;
+                }
+                if p.leader() < 0 {
+                    offline_partitions += 1;
+                }
                 partitions.push(Partition::new(p.id(), p.leader(), p.replicas().to_owned(), p.isr().to_owned(),
                                                p.error().map(|e| rderror::resp_err_description(e))));
             }
             partitions.sort_by(|a, b| a.id.cmp(&b.id));
-            // topics.insert(t.name().to_owned(), partitions);
-            self.topic_cache.insert((self.cluster_id.to_owned(), topic.name().to_owned()), partitions)
+            topics.insert(topic.name().to_owned(), partitions);
+        }
+        let topic_count = topics.len() as i64;
+
+        // Watermark fetches run one partition at a time on this cluster's
+        // consumer (no thread pool, no parallelism) and fail independently
+        // per partition, so retrying the whole batch
+        // here would re-scan every partition on one bad partition alone and
+        // risk stalling the fetch cycle rather than protecting it.
+        fetch_watermarks(&self.cluster_id, &consumer, &mut topics, 5000);
+
+        for (topic_name, partitions) in topics {
+            self.topic_cache.insert((self.cluster_id.to_owned(), topic_name), partitions)
                 .chain_err(|| "Failed to insert broker information in cache")?;
         }
 
         // Fetch groups
-        for group in fetch_groups(consumer, 30000)? {
+        let groups = retry_with_backoff(&format!("Group list fetch, cluster: {}", self.cluster_id), || {
+            fetch_groups(&consumer, 30000)
+        })?;
+        let mut group_count = 0i64;
+        for group in groups {
+            group_count += 1;
+            let offsets = fetch_group_offsets(&self.group_consumers, &self.boostrap_servers, &own_metadata, &group, 30000)
+                .unwrap_or_else(|err| {
+                    warn!("Failed to fetch group offsets, cluster: {}, group: {}, error: {}",
+                          self.cluster_id, group.name, err);
+                    Vec::new()
+                });
+            self.group_offsets_cache.insert((self.cluster_id.to_owned(), group.name.to_owned()), offsets);
             self.group_cache.insert((self.cluster_id.to_owned(), group.name.to_owned()), group);
         }
 
+        if let Some(ref sink) = self.metrics_sink {
+            let gauges = ClusterGauges {
+                broker_count: own_metadata.brokers.len() as i64,
+                topic_count: topic_count,
+                partition_count: partition_count,
+                under_replicated_partitions: under_replicated_partitions,
+                offline_partitions: offline_partitions,
+                group_count: group_count
+            };
+            if let Err(err) = sink.send_cluster_gauges(&self.cluster_id, &gauges) {
+                warn!("Failed to send cluster metrics, cluster: {}, error: {}", self.cluster_id, err);
+            }
+        }
+
         Ok(())
     }
 }
 
+impl ScheduledTask for MetadataFetcherTask {
+    fn run(&self) -> Result<()> {
+        let result = self.run_once();
+
+        let mut status = self.status.lock().unwrap();
+        match result {
+            Ok(()) => {
+                status.last_success = UTC::now();
+                status.last_error = None;
+                status.consecutive_failures = 0;
+            },
+            Err(ref err) => {
+                status.last_error = Some(err.to_string());
+                status.consecutive_failures += 1;
+            }
+        }
+        if let Err(err) = self.fetch_status_cache.insert(self.cluster_id.to_owned(), status.clone()) {
+            warn!("Failed to update fetch status cache, cluster: {}, error: {}", self.cluster_id, err);
+        }
+
+        result
+    }
+}
+
 pub struct MetadataFetcher {
     scheduler: Scheduler<ClusterId, MetadataFetcherTask>,
     cache: ReplicatedMap<ClusterId, Arc<Metadata>>,
     broker_cache: ReplicatedMap<ClusterId, Vec<Broker>>,
     topic_cache: ReplicatedMap<(ClusterId, TopicName), Vec<Partition>>,
-    group_cache: ReplicatedMap<(ClusterId, String), Group>
+    group_cache: ReplicatedMap<(ClusterId, String), Group>,
+    group_offsets_cache: ReplicatedMap<(ClusterId, String), Vec<GroupOffset>>,
+    fetch_status_cache: ReplicatedMap<ClusterId, FetchStatus>,
+    consumers: BTreeMap<ClusterId, LazyConsumer>,
+    bootstrap_servers: BTreeMap<ClusterId, String>,
+    admin_clients: BTreeMap<ClusterId, LazyAdminClient>,
+    metrics_sink: Option<Arc<MetricsSink>>,
+    object_store: Option<Arc<ObjectStore>>
 }
 
 impl MetadataFetcher {
@@ -233,6 +618,8 @@ impl MetadataFetcher {
         broker_cache: ReplicatedMap<ClusterId, Vec<Broker>>,
         topic_cache: ReplicatedMap<(ClusterId, TopicName), Vec<Partition>>,
         group_cache: ReplicatedMap<(ClusterId, String), Group>,
+        group_offsets_cache: ReplicatedMap<(ClusterId, String), Vec<GroupOffset>>,
+        fetch_status_cache: ReplicatedMap<ClusterId, FetchStatus>,
         interval: Duration
     ) -> MetadataFetcher {
         MetadataFetcher {
@@ -241,16 +628,105 @@ impl MetadataFetcher {
             broker_cache: broker_cache,
             topic_cache: topic_cache,
             group_cache: group_cache,
+            group_offsets_cache: group_offsets_cache,
+            fetch_status_cache: fetch_status_cache,
+            consumers: BTreeMap::new(),
+            bootstrap_servers: BTreeMap::new(),
+            admin_clients: BTreeMap::new(),
+            metrics_sink: None,
+            object_store: None,
         }
     }
 
+    /// Enables historical metadata snapshots for every cluster added after
+    /// this call. `backend` is `"memory"` or `"s3"`.
+    pub fn with_storage(mut self, backend: &str, s3_bucket: Option<&str>, s3_region: Option<&str>) -> Result<MetadataFetcher> {
+        self.object_store = Some(storage::build(backend, s3_bucket, s3_region)?);
+        Ok(self)
+    }
+
+    /// Lists the metadata snapshots recorded for `cluster_id` whose
+    /// `refresh_time` falls within `time_range`, oldest first.
+    pub fn history(&self, cluster_id: &ClusterId, time_range: (DateTime<UTC>, DateTime<UTC>)) -> Result<Vec<Metadata>> {
+        let store = self.object_store.as_ref()
+            .ok_or_else(|| "No storage backend configured")?;
+        let prefix = format!("{}/metadata/", cluster_id);
+
+        let mut snapshots = Vec::new();
+        for key in store.list(&prefix)? {
+            let bytes = store.get(&key)?;
+            let metadata: Metadata = serde_json::from_slice(&bytes)
+                .chain_err(|| format!("Failed to deserialize metadata snapshot: {}", key))?;
+            if metadata.refresh_time >= time_range.0 && metadata.refresh_time <= time_range.1 {
+                snapshots.push(metadata);
+            }
+        }
+        snapshots.sort_by(|a, b| a.refresh_time.cmp(&b.refresh_time));
+        Ok(snapshots)
+    }
+
+    /// Enables periodic StatsD gauge emission for every cluster added after
+    /// this call. `address` is a `host:port` StatsD endpoint.
+    pub fn with_metrics(mut self, address: &str, prefix: &str) -> Result<MetadataFetcher> {
+        self.metrics_sink = Some(Arc::new(MetricsSink::new(address, prefix)?));
+        Ok(self)
+    }
+
     pub fn add_cluster(&mut self, cluster_id: &ClusterId, boostrap_servers: &str) -> Result<()> {
-        let mut task = MetadataFetcherTask::new(
-            cluster_id, boostrap_servers, self.cache.alias(), self.broker_cache.alias(),
-            self.topic_cache.alias(), self.group_cache.alias());
-        task.create_consumer();
+        let consumer: LazyConsumer = Arc::new(Mutex::new(None));
+        let task = MetadataFetcherTask::new(
+            cluster_id, boostrap_servers, consumer.clone(), self.cache.alias(), self.broker_cache.alias(),
+            self.topic_cache.alias(), self.group_cache.alias(), self.group_offsets_cache.alias(),
+            self.metrics_sink.clone(), self.object_store.clone(), self.fetch_status_cache.alias());
+        self.consumers.insert(cluster_id.to_owned(), consumer);
+        self.bootstrap_servers.insert(cluster_id.to_owned(), boostrap_servers.to_owned());
+        self.admin_clients.insert(cluster_id.to_owned(), Arc::new(Mutex::new(None)));
         // TODO: scheduler should receive a lambda
         self.scheduler.add_task(cluster_id.to_owned(), task);
         Ok(())
     }
+
+    fn admin_client(&self, cluster_id: &ClusterId) -> Result<Arc<AdminClient<DefaultClientContext>>> {
+        let admin_client = self.admin_clients.get(cluster_id)
+            .ok_or_else(|| format!("Unknown cluster: {}", cluster_id))?;
+        let boostrap_servers = self.bootstrap_servers.get(cluster_id)
+            .ok_or_else(|| format!("Unknown cluster: {}", cluster_id))?;
+        ensure_admin_client(admin_client, boostrap_servers)
+    }
+
+    /// Forces an out-of-band metadata refresh, so the cache reflects an admin
+    /// change immediately rather than waiting for the next scheduled tick.
+    fn refresh_cluster(&self, cluster_id: &ClusterId) -> Result<()> {
+        let consumer = self.consumers.get(cluster_id)
+            .ok_or_else(|| format!("Unknown cluster: {}", cluster_id))?;
+        let boostrap_servers = self.bootstrap_servers.get(cluster_id)
+            .ok_or_else(|| format!("Unknown cluster: {}", cluster_id))?;
+        let consumer = ensure_consumer(consumer, boostrap_servers)
+            .chain_err(|| format!("Metadata refresh failed, cluster: {}", cluster_id))?;
+        let metadata = fetch_metadata(&consumer, 30000)
+            .chain_err(|| format!("Metadata refresh failed, cluster: {}", cluster_id))?;
+        self.cache.insert(cluster_id.to_owned(), Arc::new(metadata))
+            .chain_err(|| "Failed to create new metadata container to cache")?;
+        Ok(())
+    }
+
+    pub fn create_topic(&self, cluster_id: &ClusterId, spec: NewTopicSpec) -> Result<()> {
+        admin::create_topic(&self.admin_client(cluster_id)?, &spec, 30000)?;
+        self.refresh_cluster(cluster_id)
+    }
+
+    pub fn delete_topic(&self, cluster_id: &ClusterId, topic: &str) -> Result<()> {
+        admin::delete_topic(&self.admin_client(cluster_id)?, topic, 30000)?;
+        self.refresh_cluster(cluster_id)
+    }
+
+    pub fn create_partitions(&self, cluster_id: &ClusterId, spec: NewPartitionsSpec) -> Result<()> {
+        admin::create_partitions(&self.admin_client(cluster_id)?, &spec, 30000)?;
+        self.refresh_cluster(cluster_id)
+    }
+
+    pub fn alter_configs(&self, cluster_id: &ClusterId, spec: AlterConfigSpec) -> Result<()> {
+        admin::alter_configs(&self.admin_client(cluster_id)?, &spec, 30000)?;
+        self.refresh_cluster(cluster_id)
+    }
 }
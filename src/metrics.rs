@@ -0,0 +1,53 @@
+use std::net::UdpSocket;
+
+use error::*;
+
+/// Gauges computed from a single cluster's freshly refreshed metadata cache.
+pub struct ClusterGauges {
+    pub broker_count: i64,
+    pub topic_count: i64,
+    pub partition_count: i64,
+    pub under_replicated_partitions: i64,
+    pub offline_partitions: i64,
+    pub group_count: i64
+}
+
+/// Emits cluster health gauges to a StatsD endpoint over UDP, batched into a
+/// single datagram per cluster. Sends are best-effort: callers should log a
+/// failure and carry on rather than treat it as a fetch failure.
+pub struct MetricsSink {
+    socket: UdpSocket,
+    address: String,
+    prefix: String
+}
+
+impl MetricsSink {
+    pub fn new(address: &str, prefix: &str) -> Result<MetricsSink> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .chain_err(|| "Failed to bind UDP socket for StatsD metrics")?;
+        Ok(MetricsSink {
+            socket: socket,
+            address: address.to_owned(),
+            prefix: prefix.to_owned()
+        })
+    }
+
+    pub fn send_cluster_gauges(&self, cluster_id: &str, gauges: &ClusterGauges) -> Result<()> {
+        let lines = vec![
+            self.gauge_line(cluster_id, "broker_count", gauges.broker_count),
+            self.gauge_line(cluster_id, "topic_count", gauges.topic_count),
+            self.gauge_line(cluster_id, "partition_count", gauges.partition_count),
+            self.gauge_line(cluster_id, "under_replicated_partitions", gauges.under_replicated_partitions),
+            self.gauge_line(cluster_id, "offline_partitions", gauges.offline_partitions),
+            self.gauge_line(cluster_id, "group_count", gauges.group_count),
+        ];
+        let payload = lines.join("\n");
+        self.socket.send_to(payload.as_bytes(), &self.address)
+            .chain_err(|| format!("Failed to send StatsD metrics to {}", self.address))?;
+        Ok(())
+    }
+
+    fn gauge_line(&self, cluster_id: &str, metric: &str, value: i64) -> String {
+        format!("{}.{}.{}:{}|g", self.prefix, cluster_id, metric, value)
+    }
+}
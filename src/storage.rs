@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use rusoto_core::Region;
+use rusoto_s3::{GetObjectRequest, ListObjectsV2Request, PutObjectRequest, S3, S3Client};
+
+use error::*;
+
+/// A small key/value blob store, abstracting over where metadata snapshots
+/// actually live so a single-node deployment needs no external dependency.
+pub trait ObjectStore: Send + Sync {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()>;
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+    fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+/// In-process object store. History does not survive a restart, but it
+/// requires no configuration.
+pub struct MemoryObjectStore {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>
+}
+
+impl MemoryObjectStore {
+    pub fn new() -> MemoryObjectStore {
+        MemoryObjectStore {
+            objects: Mutex::new(BTreeMap::new())
+        }
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+        self.objects.lock().unwrap().insert(key.to_owned(), bytes);
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.objects.lock().unwrap().get(key).cloned()
+            .ok_or_else(|| format!("No such object: {}", key).into())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self.objects.lock().unwrap().keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+/// S3-backed object store, for deployments that want metadata history to
+/// survive restarts and be shared across nodes.
+pub struct S3ObjectStore {
+    client: S3Client,
+    bucket: String
+}
+
+impl S3ObjectStore {
+    pub fn new(bucket: &str, region: Region) -> S3ObjectStore {
+        S3ObjectStore {
+            client: S3Client::new(region),
+            bucket: bucket.to_owned()
+        }
+    }
+}
+
+impl ObjectStore for S3ObjectStore {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+        let request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_owned(),
+            body: Some(bytes.into()),
+            ..Default::default()
+        };
+        self.client.put_object(request).sync()
+            .chain_err(|| format!("Failed to put object: {}", key))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_owned(),
+            ..Default::default()
+        };
+        let output = self.client.get_object(request).sync()
+            .chain_err(|| format!("Failed to get object: {}", key))?;
+        let mut bytes = Vec::new();
+        output.body
+            .ok_or_else(|| format!("Object has no body: {}", key))?
+            .into_blocking_read()
+            .read_to_end(&mut bytes)
+            .chain_err(|| format!("Failed to read object body: {}", key))?;
+        Ok(bytes)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let request = ListObjectsV2Request {
+            bucket: self.bucket.clone(),
+            prefix: Some(prefix.to_owned()),
+            ..Default::default()
+        };
+        let output = self.client.list_objects_v2(request).sync()
+            .chain_err(|| format!("Failed to list objects with prefix: {}", prefix))?;
+        Ok(output.contents.unwrap_or_default().into_iter()
+            .filter_map(|object| object.key)
+            .collect())
+    }
+}
+
+/// Builds the configured object store backend. `kind` is `"memory"` or
+/// `"s3"`; the S3 backend additionally requires `bucket`, and `region`
+/// defaults to `us-east-1` when not given.
+pub fn build(kind: &str, bucket: Option<&str>, region: Option<&str>) -> Result<Arc<ObjectStore>> {
+    match kind {
+        "memory" => Ok(Arc::new(MemoryObjectStore::new())),
+        "s3" => {
+            let bucket = bucket.ok_or_else(|| "S3 storage backend requires a bucket name")?;
+            let region = region.unwrap_or("us-east-1").parse::<Region>()
+                .chain_err(|| "Invalid S3 region")?;
+            Ok(Arc::new(S3ObjectStore::new(bucket, region)))
+        },
+        other => bail!(format!("Unknown storage backend: {}", other))
+    }
+}